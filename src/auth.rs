@@ -0,0 +1,138 @@
+//! Public-key authorization against an OpenSSH `authorized_keys`-format file.
+//!
+//! Only fingerprints present in the file are accepted; the bound username
+//! comes from the allowlist entry, not whatever the SSH client claims, so
+//! identities can't be spoofed.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use tokio::sync::RwLock;
+
+/// Maps key fingerprint -> the username that fingerprint is allowed to use.
+pub struct AuthorizedKeys {
+    path: PathBuf,
+    entries: RwLock<HashMap<String, String>>,
+}
+
+impl AuthorizedKeys {
+    /// Load and parse `path`.
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let entries = Self::parse_file(&path)?;
+        Ok(Self {
+            path,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// An allowlist with no entries, for when `path` can't be loaded yet.
+    pub fn empty(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn parse_file(path: &std::path::Path) -> anyhow::Result<HashMap<String, String>> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut entries = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // `<key-type> <base64-key> <username>`, as in sshd's authorized_keys.
+            let mut fields = line.split_whitespace();
+            let Some(_key_type) = fields.next() else {
+                continue;
+            };
+            let Some(base64_key) = fields.next() else {
+                continue;
+            };
+            let Some(username) = fields.next() else {
+                continue;
+            };
+            match russh_keys::parse_public_key_base64(base64_key) {
+                Ok(key) => {
+                    entries.insert(key.fingerprint(), username.to_string());
+                }
+                Err(e) => {
+                    tracing::warn!(error = ?e, "skipping unparseable authorized_keys entry");
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The username bound to `fingerprint`, if it's on the allowlist.
+    pub async fn username_for(&self, fingerprint: &str) -> Option<String> {
+        self.entries.read().await.get(fingerprint).cloned()
+    }
+
+    /// Re-read the file from disk, replacing the in-memory map. Meant to be
+    /// triggered on SIGHUP so operators can add/remove users without a restart.
+    pub async fn reload(&self) -> anyhow::Result<()> {
+        let entries = Self::parse_file(&self.path)?;
+        *self.entries.write().await = entries;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // A syntactically valid ssh-ed25519 public key blob (arbitrary key bytes;
+    // only the wire format needs to parse, nothing here is used to connect).
+    const SAMPLE_KEY: &str =
+        "AAAAC3NzaC1lZDI1NTE5AAAAIAABAgMEBQYHCAkKCwwNDg8QERITFBUWFxgZGhscHR4f";
+
+    /// A temp file that's removed when dropped, so a failing assertion above
+    /// doesn't leak the file in the OS temp directory.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn with_contents(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("ssh_chat_authorized_keys_test_{name}_{}", std::process::id()));
+            std::fs::File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn parse_file_skips_blank_lines_and_comments() {
+        let file = TempFile::with_contents("comments", "\n# a comment\n   \n");
+        let entries = AuthorizedKeys::parse_file(&file.0).unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn parse_file_skips_short_lines() {
+        let file = TempFile::with_contents("short", &format!("ssh-ed25519 {SAMPLE_KEY}\n"));
+        let entries = AuthorizedKeys::parse_file(&file.0).unwrap();
+        assert!(entries.is_empty(), "a line missing the username field should be skipped");
+    }
+
+    #[test]
+    fn parse_file_skips_unparseable_keys() {
+        let file = TempFile::with_contents("garbage", "ssh-ed25519 not-valid-base64 alice\n");
+        let entries = AuthorizedKeys::parse_file(&file.0).unwrap();
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn parse_file_binds_fingerprint_to_username() {
+        let file = TempFile::with_contents("valid", &format!("ssh-ed25519 {SAMPLE_KEY} alice\n"));
+        let entries = AuthorizedKeys::parse_file(&file.0).unwrap();
+        let key = russh_keys::parse_public_key_base64(SAMPLE_KEY).unwrap();
+        assert_eq!(entries.get(&key.fingerprint()), Some(&"alice".to_string()));
+    }
+}
@@ -0,0 +1,39 @@
+//! Replays an asciicast v2 recording produced by `ssh_chat`'s session recorder,
+//! writing output events to stdout with their original inter-event delays.
+
+use std::io::Write;
+
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("usage: replay <recording.cast>"))?;
+
+    let file = tokio::fs::File::open(&path).await?;
+    let mut lines = BufReader::new(file).lines();
+
+    // First line is the asciicast header; nothing to replay from it.
+    lines.next_line().await?;
+
+    let mut last_ts = 0.0_f64;
+    while let Some(line) = lines.next_line().await? {
+        let event: Value = serde_json::from_str(&line)?;
+        let ts = event[0].as_f64().unwrap_or(last_ts);
+        let kind = event[1].as_str().unwrap_or("");
+        let data = event[2].as_str().unwrap_or("");
+
+        let delta = (ts - last_ts).max(0.0);
+        tokio::time::sleep(std::time::Duration::from_secs_f64(delta)).await;
+        last_ts = ts;
+
+        if kind == "o" {
+            print!("{data}");
+            std::io::stdout().flush()?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,17 +1,39 @@
+mod auth;
+mod message;
+mod recorder;
+mod rooms;
+mod storage;
+mod telemetry;
+
 use anyhow::anyhow;
 use async_trait::async_trait;
+use auth::AuthorizedKeys;
+use message::Message;
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Layout, Rect},
-    text::Line,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{Block, Paragraph, Wrap},
     Terminal,
 };
+use recorder::SessionRecorder;
+use rooms::{RoomRegistry, DEFAULT_ROOM};
 use russh::{server::*, Channel, ChannelId, MethodSet};
 use russh_keys::key::PublicKey;
-use std::{collections::HashMap, io::ErrorKind, ops::Neg, sync::Arc};
+use std::{collections::HashMap, io::ErrorKind, ops::Neg, path::PathBuf, sync::Arc};
+use storage::Storage;
 use strip_ansi_escapes::strip;
 use tokio::sync::{Mutex, RwLock};
+use tracing::Instrument;
+
+/// A connected user, as shown in the live presence panel.
+#[derive(Clone)]
+struct RosterEntry {
+    user: String,
+    fingerprint: String,
+    room: String,
+}
 
 #[derive(Clone)]
 struct Client {
@@ -19,18 +41,36 @@ struct Client {
     input: String,
     user: String,
     fingerprint: String,
+    current_room: String,
     scroll: i32,
 }
 
 impl Client {
-    fn render(&mut self, history: &[String]) -> std::io::Result<()> {
+    fn render(&mut self, history: &[Message], roster: &[RosterEntry]) -> std::io::Result<()> {
         self.terminal.draw(|frame| {
-            let rects = Layout::vertical([Constraint::Percentage(90), Constraint::Fill(1)])
+            let cols = Layout::horizontal([Constraint::Min(0), Constraint::Length(24)])
                 .split(frame.size());
+            let rects = Layout::vertical([Constraint::Percentage(90), Constraint::Fill(1)])
+                .split(cols[0]);
             let para = Paragraph::new(
                 history
                     .iter()
-                    .map(|s| Line::from(s.as_str()))
+                    .map(|message| {
+                        Line::from(vec![
+                            Span::styled(
+                                format!("{} ", message.sent_at.format("%H:%M")),
+                                Style::default().fg(Color::DarkGray),
+                            ),
+                            Span::styled(
+                                message.author.clone(),
+                                Style::default()
+                                    .fg(Color::Cyan)
+                                    .add_modifier(Modifier::BOLD),
+                            ),
+                            Span::raw(": "),
+                            Span::raw(message.body.clone()),
+                        ])
+                    })
                     .collect::<Vec<_>>(),
             )
             .wrap(Wrap { trim: true });
@@ -60,6 +100,25 @@ impl Client {
                     .wrap(Wrap { trim: true }),
                 rects[1],
             );
+
+            let roster_lines = roster
+                .iter()
+                .map(|entry| {
+                    Line::from(vec![
+                        Span::styled(
+                            entry.user.clone(),
+                            Style::default().add_modifier(Modifier::BOLD),
+                        ),
+                        Span::raw(format!(" #{}", entry.room)),
+                    ])
+                })
+                .collect::<Vec<_>>();
+            frame.render_widget(
+                Paragraph::new(roster_lines)
+                    .block(Block::bordered().title("Online"))
+                    .wrap(Wrap { trim: true }),
+                cols[1],
+            );
         })?;
         Ok(())
     }
@@ -67,7 +126,9 @@ impl Client {
     fn new(
         user: String,
         fingerprint: String,
-        history: &[String],
+        current_room: String,
+        history: &[Message],
+        roster: &[RosterEntry],
         handle: TerminalHandle,
     ) -> std::io::Result<Self> {
         let mut terminal = Terminal::new(CrosstermBackend::new(handle))?;
@@ -77,9 +138,10 @@ impl Client {
             input: "".into(),
             user,
             fingerprint,
+            current_room,
             scroll: 0,
         };
-        client.render(history)?;
+        client.render(history, roster)?;
         Ok(client)
     }
 }
@@ -89,6 +151,7 @@ struct TerminalHandle {
     handle: Handle,
     sink: Vec<u8>,
     channel_id: ChannelId,
+    recorder: Option<Arc<Mutex<SessionRecorder>>>,
 }
 
 impl std::io::Write for TerminalHandle {
@@ -100,11 +163,17 @@ impl std::io::Write for TerminalHandle {
     fn flush(&mut self) -> std::io::Result<()> {
         let handle = self.handle.clone();
         let channel_id = self.channel_id;
-        let data = self.sink.clone().into();
+        let data: Vec<u8> = self.sink.clone();
+        let recorder = self.recorder.clone();
         futures::executor::block_on(async move {
-            let result = handle.data(channel_id, data).await;
-            if result.is_err() {
-                eprintln!("Failed to send data: {:?}", result);
+            if let Some(recorder) = recorder {
+                if let Err(e) = recorder.lock().await.record_output(&data).await {
+                    tracing::warn!(error = ?e, "failed to record session output");
+                }
+            }
+            let result = handle.data(channel_id, data.into()).await;
+            if let Err(e) = result {
+                tracing::warn!(error = ?e, "failed to send data");
             }
         });
 
@@ -113,20 +182,106 @@ impl std::io::Write for TerminalHandle {
     }
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 struct AppServer {
     clients: Arc<Mutex<HashMap<usize, Client>>>,
-    history: Arc<RwLock<Vec<String>>>,
+    rooms: Arc<RwLock<RoomRegistry>>,
     keys: Arc<Mutex<HashMap<usize, (String, PublicKey)>>>,
+    recorders: Arc<Mutex<HashMap<usize, Arc<Mutex<SessionRecorder>>>>>,
+    authorized_keys: Arc<AuthorizedKeys>,
+    spans: Arc<Mutex<HashMap<usize, tracing::Span>>>,
+    message_counts: Arc<Mutex<HashMap<usize, u64>>>,
+    record_dir: Option<PathBuf>,
+    storage: Option<Arc<Storage>>,
+    backlog_len: usize,
     id: usize,
 }
 
+/// Default number of messages kept in the in-memory history cache and
+/// replayed to newly-joined clients.
+const DEFAULT_BACKLOG_LEN: usize = 200;
+
+impl Default for AppServer {
+    fn default() -> Self {
+        Self {
+            clients: Default::default(),
+            rooms: Default::default(),
+            keys: Default::default(),
+            recorders: Default::default(),
+            authorized_keys: Arc::new(AuthorizedKeys::empty("authorized_keys")),
+            spans: Default::default(),
+            message_counts: Default::default(),
+            record_dir: None,
+            storage: None,
+            backlog_len: DEFAULT_BACKLOG_LEN,
+            id: 0,
+        }
+    }
+}
+
 impl AppServer {
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Pull the value following `--flag` out of the process arguments.
+    fn arg_value(flag: &str) -> Option<String> {
+        let mut args = std::env::args();
+        while let Some(arg) = args.next() {
+            if arg == flag {
+                return args.next();
+            }
+        }
+        None
+    }
+
     pub async fn run(&mut self) -> Result<(), anyhow::Error> {
+        self.record_dir = Self::arg_value("--record-dir").map(PathBuf::from);
+        self.backlog_len = Self::arg_value("--backlog")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(DEFAULT_BACKLOG_LEN);
+
+        let db_path = Self::arg_value("--db").unwrap_or_else(|| "ssh_chat.db".into());
+        let storage = Arc::new(Storage::open(&db_path)?);
+        {
+            let history = storage.recent_messages(DEFAULT_ROOM, self.backlog_len)?;
+            self.rooms.write().await.ensure_room(DEFAULT_ROOM, history);
+        }
+        self.storage = Some(storage);
+
+        let authorized_keys_path =
+            Self::arg_value("--authorized-keys").unwrap_or_else(|| "authorized_keys".into());
+        let authorized_keys = Arc::new(AuthorizedKeys::load(&authorized_keys_path).unwrap_or_else(|e| {
+            tracing::error!(
+                path = %authorized_keys_path,
+                error = ?e,
+                "failed to load authorized_keys; rejecting all logins until reloaded"
+            );
+            AuthorizedKeys::empty(authorized_keys_path.clone())
+        }));
+        self.authorized_keys = authorized_keys.clone();
+
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let authorized_keys = authorized_keys.clone();
+            tokio::spawn(async move {
+                let mut sighup = match signal(SignalKind::hangup()) {
+                    Ok(sighup) => sighup,
+                    Err(e) => {
+                        tracing::error!(error = ?e, "failed to install SIGHUP handler");
+                        return;
+                    }
+                };
+                while sighup.recv().await.is_some() {
+                    match authorized_keys.reload().await {
+                        Ok(()) => tracing::info!("reloaded authorized_keys"),
+                        Err(e) => tracing::warn!(error = ?e, "failed to reload authorized_keys"),
+                    }
+                }
+            });
+        }
+
         let config = Config {
             inactivity_timeout: Some(std::time::Duration::from_secs(3600)),
             auth_rejection_time: std::time::Duration::from_secs(3),
@@ -140,6 +295,201 @@ impl AppServer {
             .await?;
         Ok(())
     }
+
+    /// The tracing span for `self.id`'s connection, creating a disconnected
+    /// one (with the fingerprint recorded but the username still empty) the
+    /// first time a handler runs for it.
+    async fn span_for(&self, fingerprint: &str) -> tracing::Span {
+        let mut spans = self.spans.lock().await;
+        spans
+            .entry(self.id)
+            .or_insert_with(|| {
+                tracing::info_span!(
+                    "connection",
+                    id = self.id,
+                    user = tracing::field::Empty,
+                    fingerprint = %fingerprint,
+                )
+            })
+            .clone()
+    }
+
+    /// Snapshot of who's currently connected, for the presence panel and `/who`.
+    fn roster_entries(clients: &HashMap<usize, Client>) -> Vec<RosterEntry> {
+        let mut roster: Vec<_> = clients
+            .values()
+            .map(|client| RosterEntry {
+                user: client.user.clone(),
+                fingerprint: client.fingerprint.clone(),
+                room: client.current_room.clone(),
+            })
+            .collect();
+        roster.sort_by(|a, b| a.user.cmp(&b.user));
+        roster
+    }
+
+    /// Re-render every connected client, e.g. after a join or disconnect
+    /// (including a dropped connection, via `channel_close`/`channel_eof`,
+    /// not only an explicit `[3]`) changes who's in the presence panel.
+    async fn broadcast_presence(&self, clients: &mut HashMap<usize, Client>) -> anyhow::Result<()> {
+        let roster = Self::roster_entries(clients);
+        let rooms = self.rooms.read().await;
+        for client in clients.values_mut() {
+            let history = rooms.history(&client.current_room).to_vec();
+            client.render(&history, &roster)?;
+        }
+        Ok(())
+    }
+
+    /// Tear down everything tracked for `self.id`: leave its room, drop it
+    /// from `clients`, close its recording, and re-render the presence panel
+    /// for everyone left. Called from both the `[3]` (Ctrl-C) branch of
+    /// `data` and the channel lifecycle handlers, so it's a no-op if the
+    /// client was already torn down by whichever fired first.
+    async fn disconnect_client(&self, clients: &mut HashMap<usize, Client>) -> anyhow::Result<()> {
+        let Some(client) = clients.remove(&self.id) else {
+            return Ok(());
+        };
+        self.rooms.write().await.leave(&client.current_room, self.id);
+        self.spans.lock().await.remove(&self.id);
+        self.message_counts.lock().await.remove(&self.id);
+        // `client` (and the `TerminalHandle` clone it carries) holds another
+        // `Arc` to the recorder below; drop it first so the `try_unwrap`
+        // actually succeeds instead of silently skipping the flush/close.
+        drop(client);
+        if let Some(recorder) = self.recorders.lock().await.remove(&self.id) {
+            match Arc::try_unwrap(recorder) {
+                Ok(recorder) => {
+                    if let Err(e) = recorder.into_inner().close().await {
+                        tracing::warn!(error = ?e, "failed to close session recording");
+                    }
+                }
+                Err(recorder) => {
+                    if let Err(e) = recorder.lock().await.flush().await {
+                        tracing::warn!(error = ?e, "failed to flush session recording");
+                    }
+                }
+            }
+        }
+        self.broadcast_presence(clients).await?;
+        tracing::info!("client disconnected");
+        Ok(())
+    }
+
+    /// Dispatch a `/`-prefixed line typed by the client at `self.id`.
+    async fn handle_command(
+        &self,
+        clients: &mut HashMap<usize, Client>,
+        room: &str,
+        author: &str,
+        cmd: &str,
+        rest: &str,
+    ) -> anyhow::Result<()> {
+        match cmd {
+            "join" if !rest.is_empty() => {
+                let new_room = rest.to_string();
+                {
+                    let mut rooms = self.rooms.write().await;
+                    rooms.leave(room, self.id);
+                    if !rooms.contains(&new_room) {
+                        let history = match &self.storage {
+                            Some(storage) => storage
+                                .recent_messages(&new_room, self.backlog_len)
+                                .unwrap_or_default(),
+                            None => Vec::new(),
+                        };
+                        rooms.ensure_room(&new_room, history);
+                    }
+                    rooms.join(&new_room, self.id);
+                }
+                if let Some(client) = clients.get_mut(&self.id) {
+                    client.current_room = new_room.clone();
+                }
+                self.broadcast_presence(clients).await?;
+            }
+            "leave" => {
+                if room != DEFAULT_ROOM {
+                    {
+                        let mut rooms = self.rooms.write().await;
+                        rooms.leave(room, self.id);
+                        rooms.join(DEFAULT_ROOM, self.id);
+                    }
+                    if let Some(client) = clients.get_mut(&self.id) {
+                        client.current_room = DEFAULT_ROOM.into();
+                    }
+                }
+                self.broadcast_presence(clients).await?;
+            }
+            "rooms" => {
+                let names = self.rooms.read().await.room_names();
+                let roster = Self::roster_entries(clients);
+                if let Some(client) = clients.get_mut(&self.id) {
+                    let mut history = self.rooms.read().await.history(room).to_vec();
+                    history.push(Message {
+                        author: "system".into(),
+                        body: format!("rooms: {}", names.join(", ")),
+                        sent_at: chrono::Local::now(),
+                    });
+                    client.render(&history, &roster)?;
+                }
+            }
+            "who" => {
+                let roster = Self::roster_entries(clients);
+                let listing = roster
+                    .iter()
+                    .map(|entry| format!("{} [{}] #{}", entry.user, entry.fingerprint, entry.room))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if let Some(client) = clients.get_mut(&self.id) {
+                    let mut history = self.rooms.read().await.history(room).to_vec();
+                    history.push(Message {
+                        author: "system".into(),
+                        body: format!("who: {listing}"),
+                        sent_at: chrono::Local::now(),
+                    });
+                    client.render(&history, &roster)?;
+                }
+            }
+            "msg" if !rest.is_empty() => {
+                let mut parts = rest.splitn(2, ' ');
+                let target_user = parts.next().unwrap_or("");
+                let text = parts.next().unwrap_or("").trim();
+                if !text.is_empty() {
+                    let whisper = Message {
+                        author: author.into(),
+                        body: format!("(whisper to {target_user}) {text}"),
+                        sent_at: chrono::Local::now(),
+                    };
+                    let target_id = clients
+                        .iter()
+                        .find(|(_, c)| c.user == target_user)
+                        .map(|(id, _)| *id);
+                    let roster = Self::roster_entries(clients);
+
+                    if let Some(client) = clients.get_mut(&self.id) {
+                        let mut history = self.rooms.read().await.history(room).to_vec();
+                        history.push(whisper.clone());
+                        client.render(&history, &roster)?;
+                    }
+                    if let Some(target_id) = target_id {
+                        if let Some(target) = clients.get_mut(&target_id) {
+                            let mut history = self.rooms.read().await.history(&target.current_room).to_vec();
+                            history.push(whisper);
+                            target.render(&history, &roster)?;
+                        }
+                    }
+                }
+            }
+            _ => {
+                let roster = Self::roster_entries(clients);
+                if let Some(client) = clients.get_mut(&self.id) {
+                    let history = self.rooms.read().await.history(room).to_vec();
+                    client.render(&history, &roster)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Server for AppServer {
@@ -160,39 +510,132 @@ impl Handler for AppServer {
         channel: Channel<Msg>,
         session: &mut Session,
     ) -> Result<bool, Self::Error> {
-        {
+        let (user, fingerprint) = {
+            let keys = self.keys.lock().await;
+            let (user, key) = keys.get(&self.id).ok_or(anyhow!(ErrorKind::NotFound))?;
+            (user.clone(), key.fingerprint())
+        };
+        let span = self.span_for(&fingerprint).await;
+        span.record("user", user.as_str());
+
+        async move {
+            tracing::info!("client connected");
             let mut clients = self.clients.lock().await;
+
+            let recorder = if let Some(dir) = &self.record_dir {
+                match SessionRecorder::create(dir, self.id, 80, 24).await {
+                    Ok(recorder) => {
+                        let recorder = Arc::new(Mutex::new(recorder));
+                        self.recorders.lock().await.insert(self.id, recorder.clone());
+                        Some(recorder)
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = ?e, "failed to start session recording");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
             let terminal_handle = TerminalHandle {
                 handle: session.handle(),
                 sink: Vec::new(),
                 channel_id: channel.id(),
+                recorder,
             };
 
-            let (user, fingerprint) = {
-                let keys = self.keys.lock().await;
-                let (user, key) = keys.get(&self.id).ok_or(anyhow!(ErrorKind::NotFound))?;
-                (user.clone(), key.fingerprint())
+            let history = {
+                let mut rooms = self.rooms.write().await;
+                rooms.join(DEFAULT_ROOM, self.id);
+                rooms.history(DEFAULT_ROOM).to_vec()
             };
+            let roster = Self::roster_entries(&clients);
 
             clients.insert(
                 self.id,
                 Client::new(
                     user,
                     fingerprint,
-                    &self.history.read().await,
+                    DEFAULT_ROOM.into(),
+                    &history,
+                    &roster,
                     terminal_handle.clone(),
                 )?,
             );
+
+            self.broadcast_presence(&mut clients).await?;
+            Ok(true)
         }
-        Ok(true)
+        .instrument(span)
+        .await
     }
 
-    async fn auth_publickey(&mut self, user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
-        {
-            let mut keys = self.keys.lock().await;
-            keys.insert(self.id, (user.into(), key.clone()));
+    /// Fires when the underlying channel tears down, whether or not the
+    /// client sent Ctrl-C first (the common case: the terminal just closes
+    /// or the TCP connection drops). Idempotent with the `[3]` branch of
+    /// `data` so whichever runs first does the teardown.
+    async fn channel_close(&mut self, _channel: ChannelId, _session: &mut Session) -> Result<(), Self::Error> {
+        let fingerprint = {
+            let clients = self.clients.lock().await;
+            clients.get(&self.id).map(|client| client.fingerprint.clone())
+        };
+        let Some(fingerprint) = fingerprint else {
+            return Ok(());
+        };
+        let span = self.span_for(&fingerprint).await;
+
+        async move {
+            let mut clients = self.clients.lock().await;
+            self.disconnect_client(&mut clients).await
         }
-        Ok(Auth::Accept)
+        .instrument(span)
+        .await
+    }
+
+    /// Some clients send EOF without ever following up with a channel close;
+    /// without this, those sessions would linger "Online" in the presence
+    /// panel and `/who` until the server restarts.
+    async fn channel_eof(&mut self, _channel: ChannelId, _session: &mut Session) -> Result<(), Self::Error> {
+        let fingerprint = {
+            let clients = self.clients.lock().await;
+            clients.get(&self.id).map(|client| client.fingerprint.clone())
+        };
+        let Some(fingerprint) = fingerprint else {
+            return Ok(());
+        };
+        let span = self.span_for(&fingerprint).await;
+
+        async move {
+            let mut clients = self.clients.lock().await;
+            self.disconnect_client(&mut clients).await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn auth_publickey(&mut self, _user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        let fingerprint = key.fingerprint();
+        let span = self.span_for(&fingerprint).await;
+
+        async move {
+            match self.authorized_keys.username_for(&fingerprint).await {
+                Some(user) => {
+                    tracing::info!("public key accepted");
+                    let mut keys = self.keys.lock().await;
+                    keys.insert(self.id, (user, key.clone()));
+                    Ok(Auth::Accept)
+                }
+                None => {
+                    tracing::warn!("public key rejected: fingerprint not on allowlist");
+                    Ok(Auth::Reject {
+                        proceed_with_methods: None,
+                    })
+                }
+            }
+        }
+        .instrument(span)
+        .await
     }
 
     async fn data(
@@ -201,58 +644,110 @@ impl Handler for AppServer {
         data: &[u8],
         session: &mut Session,
     ) -> Result<(), Self::Error> {
-        {
-            let history = self.history.clone();
+        let fingerprint = {
+            let clients = self.clients.lock().await;
+            clients
+                .get(&self.id)
+                .ok_or(anyhow!(ErrorKind::NotFound))?
+                .fingerprint
+                .clone()
+        };
+        let span = self.span_for(&fingerprint).await;
+
+        async move {
             let mut clients = self.clients.lock().await;
             let client = clients
                 .get_mut(&self.id)
                 .ok_or(anyhow!(ErrorKind::NotFound))?;
             match data {
                 [3] => {
-                    clients.remove(&self.id);
                     session.close(channel);
+                    self.disconnect_client(&mut clients).await?;
                 }
                 [13] => {
-                    {
-                        let mut history = history.write().await;
-                        history.push(client.fingerprint.clone());
-                        history.push(format!("{}: {}", client.user, client.input));
-                        history.push("".into());
-                    }
-                    client.input = "".into();
-                    for (_, client) in clients.iter_mut() {
-                        client.render(&history.read().await)?;
+                    let author = client.user.clone();
+                    let fingerprint = client.fingerprint.clone();
+                    let room = client.current_room.clone();
+                    let input = std::mem::take(&mut client.input);
+
+                    if let Some(command) = input.strip_prefix('/') {
+                        let mut parts = command.splitn(2, ' ');
+                        let cmd = parts.next().unwrap_or("");
+                        let rest = parts.next().unwrap_or("").trim().to_string();
+                        self.handle_command(&mut clients, &room, &author, cmd, &rest)
+                            .await?;
+                    } else {
+                        let message = match &self.storage {
+                            Some(storage) => storage.insert_message(&author, &fingerprint, &input, &room)?,
+                            None => Message {
+                                author: author.clone(),
+                                body: input.clone(),
+                                sent_at: chrono::Local::now(),
+                            },
+                        };
+                        self.rooms.write().await.push_message(&room, message, self.backlog_len);
+
+                        let count = {
+                            let mut counts = self.message_counts.lock().await;
+                            let count = counts.entry(self.id).or_insert(0);
+                            *count += 1;
+                            *count
+                        };
+                        tracing::info!(room = %room, count, "message sent");
+
+                        let members = self.rooms.read().await.members(&room);
+                        let history = self.rooms.read().await.history(&room).to_vec();
+                        let roster = Self::roster_entries(&clients);
+                        for member_id in members {
+                            if let Some(member) = clients.get_mut(&member_id) {
+                                member.render(&history, &roster)?;
+                            }
+                        }
                     }
                 }
                 [127] | [8] => {
                     client.input.pop();
-                    client.render(&history.read().await)?;
+                    let history = self.rooms.read().await.history(&client.current_room).to_vec();
+                    let roster = Self::roster_entries(&clients);
+                    clients.get_mut(&self.id).unwrap().render(&history, &roster)?;
                 }
                 [27, 91, 65] => {
                     client.scroll -= 1;
-                    client.render(&history.read().await)?;
+                    let history = self.rooms.read().await.history(&client.current_room).to_vec();
+                    let roster = Self::roster_entries(&clients);
+                    clients.get_mut(&self.id).unwrap().render(&history, &roster)?;
                 }
                 [27, 91, 66] => {
                     client.scroll += 1;
-                    client.render(&history.read().await)?;
+                    let history = self.rooms.read().await.history(&client.current_room).to_vec();
+                    let roster = Self::roster_entries(&clients);
+                    clients.get_mut(&self.id).unwrap().render(&history, &roster)?;
                 }
                 [27, 91, 53, 126] => {
                     client.scroll -= 10;
-                    client.render(&history.read().await)?;
+                    let history = self.rooms.read().await.history(&client.current_room).to_vec();
+                    let roster = Self::roster_entries(&clients);
+                    clients.get_mut(&self.id).unwrap().render(&history, &roster)?;
                 }
                 [27, 91, 54, 126] => {
                     client.scroll += 10;
-                    client.render(&history.read().await)?;
+                    let history = self.rooms.read().await.history(&client.current_room).to_vec();
+                    let roster = Self::roster_entries(&clients);
+                    clients.get_mut(&self.id).unwrap().render(&history, &roster)?;
                 }
                 text => {
                     client
                         .input
                         .push_str(&String::from_utf8_lossy(strip(text).as_slice()));
-                    client.render(&history.read().await)?;
+                    let history = self.rooms.read().await.history(&client.current_room).to_vec();
+                    let roster = Self::roster_entries(&clients);
+                    clients.get_mut(&self.id).unwrap().render(&history, &roster)?;
                 }
             }
+            Ok(())
         }
-        Ok(())
+        .instrument(span)
+        .await
     }
 
     async fn window_change_request(
@@ -264,25 +759,48 @@ impl Handler for AppServer {
         _: u32,
         _: &mut Session,
     ) -> Result<(), Self::Error> {
-        let mut clients = self.clients.lock().await;
-        let client = clients.get_mut(&self.id).unwrap();
-        let rect = Rect {
-            x: 0,
-            y: 0,
-            width: col_width as u16,
-            height: row_height as u16,
+        let fingerprint = {
+            let clients = self.clients.lock().await;
+            clients
+                .get(&self.id)
+                .ok_or(anyhow!(ErrorKind::NotFound))?
+                .fingerprint
+                .clone()
         };
-        client.terminal.resize(rect)?;
-        client.render(&self.history.read().await)?;
-        Ok(())
+        let span = self.span_for(&fingerprint).await;
+
+        async move {
+            if let Some(recorder) = self.recorders.lock().await.get(&self.id) {
+                if let Err(e) = recorder.lock().await.record_resize(col_width, row_height).await {
+                    tracing::warn!(error = ?e, "failed to record resize");
+                }
+            }
+
+            let mut clients = self.clients.lock().await;
+            let client = clients.get_mut(&self.id).unwrap();
+            let rect = Rect {
+                x: 0,
+                y: 0,
+                width: col_width as u16,
+                height: row_height as u16,
+            };
+            client.terminal.resize(rect)?;
+            let history = self.rooms.read().await.history(&client.current_room).to_vec();
+            let roster = Self::roster_entries(&clients);
+            clients.get_mut(&self.id).unwrap().render(&history, &roster)?;
+            Ok(())
+        }
+        .instrument(span)
+        .await
     }
 }
 
 #[tokio::main]
 async fn main() {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Trace)
-        .init();
+    let otlp_endpoint =
+        AppServer::arg_value("--otlp-endpoint").or_else(|| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok());
+    telemetry::init(otlp_endpoint);
+
     let mut server = AppServer::new();
     server.run().await.expect("Failed running server");
 }
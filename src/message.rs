@@ -0,0 +1,15 @@
+//! A structured chat message, mirroring lavina's timestamped messages.
+
+use chrono::{DateTime, Local};
+
+/// One chat message, as stored in the history cache and rendered to clients.
+///
+/// The room a message belongs to is tracked by `RoomRegistry`, not here, and
+/// the sender's fingerprint isn't needed once the message is rendered, so
+/// neither is carried on this type.
+#[derive(Clone)]
+pub struct Message {
+    pub author: String,
+    pub body: String,
+    pub sent_at: DateTime<Local>,
+}
@@ -0,0 +1,79 @@
+//! Asciicast v2 session recording, modeled on Warpgate's `TerminalRecorder`.
+//!
+//! Every byte a [`crate::TerminalHandle`] flushes to a client is mirrored into a
+//! recording file so sessions can be replayed later with the `replay` binary.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde_json::json;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+/// Captures one client's terminal output as an asciicast v2 stream.
+pub struct SessionRecorder {
+    writer: BufWriter<File>,
+    started_at: Instant,
+}
+
+impl SessionRecorder {
+    /// Create `<record_dir>/<id>.cast` and write the asciicast v2 header.
+    pub async fn create(record_dir: &Path, id: usize, width: u16, height: u16) -> std::io::Result<Self> {
+        tokio::fs::create_dir_all(record_dir).await?;
+        let path: PathBuf = record_dir.join(format!("{id}.cast"));
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)
+            .await?;
+        let mut writer = BufWriter::new(file);
+
+        let header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+        writer.write_all(header.to_string().as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+
+        Ok(Self {
+            writer,
+            started_at: Instant::now(),
+        })
+    }
+
+    /// Append an output event: the bytes a client was sent.
+    pub async fn record_output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.record_event("o", &String::from_utf8_lossy(data)).await
+    }
+
+    /// Append a resize event, as reported to `window_change_request`.
+    pub async fn record_resize(&mut self, width: u32, height: u32) -> std::io::Result<()> {
+        self.record_event("r", &format!("{width}x{height}")).await
+    }
+
+    async fn record_event(&mut self, kind: &str, data: &str) -> std::io::Result<()> {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        let line = json!([elapsed, kind, data]).to_string();
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.write_all(b"\n").await?;
+        Ok(())
+    }
+
+    /// Flush buffered events to disk without closing the file, for callers
+    /// that only hold a shared `Arc<Mutex<SessionRecorder>>` and can't
+    /// consume it.
+    pub async fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush().await
+    }
+
+    /// Flush and close the recording.
+    pub async fn close(mut self) -> std::io::Result<()> {
+        self.writer.flush().await
+    }
+}
@@ -0,0 +1,138 @@
+//! Multi-room chat state, à la lavina's rooms.
+//!
+//! Each room keeps its own message history and member set; clients only see
+//! traffic from the room they're currently in.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::message::Message;
+
+/// The room every client starts in.
+pub const DEFAULT_ROOM: &str = "general";
+
+/// A single chat room: its history cache and the ids of connected members.
+#[derive(Default)]
+pub struct Room {
+    pub history: Vec<Message>,
+    pub members: HashSet<usize>,
+}
+
+/// Tracks all known rooms by name.
+#[derive(Default)]
+pub struct RoomRegistry {
+    rooms: HashMap<String, Room>,
+}
+
+impl RoomRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names of every room currently known to the registry.
+    pub fn room_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.rooms.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.rooms.contains_key(name)
+    }
+
+    /// Create a room seeded with `history`, if it doesn't already exist.
+    pub fn ensure_room(&mut self, name: &str, history: Vec<Message>) {
+        self.rooms.entry(name.to_string()).or_insert_with(|| Room {
+            history,
+            members: HashSet::new(),
+        });
+    }
+
+    pub fn join(&mut self, name: &str, id: usize) {
+        if let Some(room) = self.rooms.get_mut(name) {
+            room.members.insert(id);
+        }
+    }
+
+    pub fn leave(&mut self, name: &str, id: usize) {
+        if let Some(room) = self.rooms.get_mut(name) {
+            room.members.remove(&id);
+        }
+    }
+
+    pub fn members(&self, name: &str) -> Vec<usize> {
+        self.rooms
+            .get(name)
+            .map(|room| room.members.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn history(&self, name: &str) -> &[Message] {
+        self.rooms
+            .get(name)
+            .map(|room| room.history.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Append `message` to `name`'s history, trimming it down to `cap` entries.
+    pub fn push_message(&mut self, name: &str, message: Message, cap: usize) {
+        if let Some(room) = self.rooms.get_mut(name) {
+            room.history.push(message);
+            if room.history.len() > cap {
+                let excess = room.history.len() - cap;
+                room.history.drain(0..excess);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    fn message(body: &str) -> Message {
+        Message {
+            author: "alice".into(),
+            body: body.into(),
+            sent_at: Local::now(),
+        }
+    }
+
+    #[test]
+    fn join_and_leave_track_membership() {
+        let mut rooms = RoomRegistry::new();
+        rooms.ensure_room(DEFAULT_ROOM, Vec::new());
+        rooms.join(DEFAULT_ROOM, 1);
+        rooms.join(DEFAULT_ROOM, 2);
+        assert_eq!(rooms.members(DEFAULT_ROOM).len(), 2);
+
+        rooms.leave(DEFAULT_ROOM, 1);
+        assert_eq!(rooms.members(DEFAULT_ROOM), vec![2]);
+    }
+
+    #[test]
+    fn leave_on_an_unknown_room_is_a_noop() {
+        let mut rooms = RoomRegistry::new();
+        rooms.leave("nowhere", 1);
+        assert!(rooms.members("nowhere").is_empty());
+    }
+
+    #[test]
+    fn push_message_trims_history_to_cap() {
+        let mut rooms = RoomRegistry::new();
+        rooms.ensure_room(DEFAULT_ROOM, Vec::new());
+        for i in 0..5 {
+            rooms.push_message(DEFAULT_ROOM, message(&i.to_string()), 3);
+        }
+        let bodies: Vec<_> = rooms.history(DEFAULT_ROOM).iter().map(|m| m.body.as_str()).collect();
+        assert_eq!(bodies, vec!["2", "3", "4"]);
+    }
+
+    #[test]
+    fn ensure_room_does_not_clobber_an_existing_rooms_history() {
+        let mut rooms = RoomRegistry::new();
+        rooms.ensure_room(DEFAULT_ROOM, vec![message("first")]);
+        rooms.ensure_room(DEFAULT_ROOM, vec![message("second")]);
+        assert_eq!(rooms.history(DEFAULT_ROOM).len(), 1);
+    }
+}
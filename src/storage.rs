@@ -0,0 +1,173 @@
+//! SQLite-backed chat history, modeled on lavina's `Storage`.
+//!
+//! Messages are persisted to a `messages` table so chat survives a server
+//! restart and late joiners can be seeded with real history instead of an
+//! empty screen.
+
+use std::sync::Mutex as StdMutex;
+
+use chrono::{Local, TimeZone};
+use rusqlite::{params, Connection};
+
+use crate::message::Message;
+
+/// Wraps a SQLite connection holding the `messages` table.
+pub struct Storage {
+    conn: StdMutex<Connection>,
+}
+
+impl Storage {
+    /// Open (or create) the database at `path`, ensure the schema exists, and
+    /// migrate it forward if it was created by an older version of the server.
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS messages (
+                id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                author      TEXT NOT NULL,
+                fingerprint TEXT NOT NULL,
+                body        TEXT NOT NULL,
+                room        TEXT NOT NULL DEFAULT 'general',
+                sent_at     INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        Self::migrate(&conn)?;
+        Ok(Self {
+            conn: StdMutex::new(conn),
+        })
+    }
+
+    /// Bring a `messages` table created by an earlier version of the server
+    /// up to the current schema. `CREATE TABLE IF NOT EXISTS` above is a
+    /// no-op against such a table, so columns added since have to be backfilled
+    /// by hand with `ALTER TABLE ... ADD COLUMN`.
+    fn migrate(conn: &Connection) -> anyhow::Result<()> {
+        let has_room = conn
+            .prepare("SELECT 1 FROM pragma_table_info('messages') WHERE name = 'room'")?
+            .exists([])?;
+        if !has_room {
+            conn.execute(
+                "ALTER TABLE messages ADD COLUMN room TEXT NOT NULL DEFAULT 'general'",
+                [],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Insert a new message, stamped with the current local time, and return
+    /// the stored record for the caller to push into the room's history cache.
+    pub fn insert_message(
+        &self,
+        author: &str,
+        fingerprint: &str,
+        body: &str,
+        room: &str,
+    ) -> anyhow::Result<Message> {
+        let sent_at = Local::now();
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO messages (author, fingerprint, body, room, sent_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![author, fingerprint, body, room, sent_at.timestamp()],
+        )?;
+        Ok(Message {
+            author: author.into(),
+            body: body.into(),
+            sent_at,
+        })
+    }
+
+    /// Load the most recent `limit` messages for `room`, oldest first.
+    pub fn recent_messages(&self, room: &str, limit: usize) -> anyhow::Result<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT author, body, sent_at FROM messages
+             WHERE room = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let mut rows = stmt
+            .query_map(params![room, limit as i64], |row| {
+                let sent_at: i64 = row.get(2)?;
+                Ok(Message {
+                    author: row.get(0)?,
+                    body: row.get(1)?,
+                    sent_at: Local.timestamp_opt(sent_at, 0).unwrap(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.reverse();
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_messages_are_oldest_first_and_room_scoped() {
+        let storage = Storage::open(":memory:").unwrap();
+        storage.insert_message("alice", "fp-a", "hi", "general").unwrap();
+        storage.insert_message("bob", "fp-b", "yo", "general").unwrap();
+        storage.insert_message("carol", "fp-c", "elsewhere", "other").unwrap();
+
+        let history = storage.recent_messages("general", 10).unwrap();
+        let bodies: Vec<_> = history.iter().map(|m| m.body.as_str()).collect();
+        assert_eq!(bodies, vec!["hi", "yo"]);
+    }
+
+    #[test]
+    fn recent_messages_respects_the_limit_while_staying_oldest_first() {
+        let storage = Storage::open(":memory:").unwrap();
+        for i in 0..5 {
+            storage
+                .insert_message("alice", "fp-a", &i.to_string(), "general")
+                .unwrap();
+        }
+
+        let history = storage.recent_messages("general", 3).unwrap();
+        let bodies: Vec<_> = history.iter().map(|m| m.body.as_str()).collect();
+        assert_eq!(bodies, vec!["2", "3", "4"]);
+    }
+
+    #[test]
+    fn open_migrates_a_pre_room_column_database() {
+        let path = std::env::temp_dir().join(format!("ssh_chat_storage_migrate_test_{}.db", std::process::id()));
+        let _cleanup = RemoveOnDrop(path.clone());
+
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute(
+                "CREATE TABLE messages (
+                    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                    author      TEXT NOT NULL,
+                    fingerprint TEXT NOT NULL,
+                    body        TEXT NOT NULL,
+                    sent_at     INTEGER NOT NULL
+                )",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO messages (author, fingerprint, body, sent_at) VALUES ('alice', 'fp', 'hi', 0)",
+                [],
+            )
+            .unwrap();
+        }
+
+        // Opening the pre-existing, room-less database through the real
+        // `Storage::open` path should migrate it rather than erroring out.
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+        let history = storage.recent_messages("general", 10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].body, "hi");
+    }
+
+    /// Deletes the wrapped path when dropped, so a temp db is cleaned up even
+    /// if an assertion above panics.
+    struct RemoveOnDrop(std::path::PathBuf);
+
+    impl Drop for RemoveOnDrop {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Structured tracing setup: a stderr `fmt` layer, plus an optional
+//! OpenTelemetry OTLP exporter so chat activity and auth failures become
+//! observable traces in a collector instead of scattered `eprintln!`s.
+
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Initialize the global tracing subscriber. When `otlp_endpoint` is set
+/// (from `--otlp-endpoint` or the standard `OTEL_EXPORTER_OTLP_ENDPOINT` env
+/// var), spans and events are also exported via OTLP to that collector.
+pub fn init(otlp_endpoint: Option<String>) {
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("trace"));
+    let fmt_layer = tracing_subscriber::fmt::layer();
+
+    let otlp_layer = otlp_endpoint.and_then(|endpoint| {
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(&endpoint),
+            )
+            .install_batch(opentelemetry_sdk::runtime::Tokio)
+            .map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer))
+            .map_err(|e| {
+                eprintln!("failed to install OTLP tracer for {endpoint}: {e:?}; continuing without it");
+            })
+            .ok()
+    });
+
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(fmt_layer)
+        .with(otlp_layer)
+        .init();
+}